@@ -0,0 +1,93 @@
+// Command execution for `-x/--exec`, modeled on fd's exec subsystem: a
+// command template is tokenized once, then each matching file's path is
+// substituted into placeholders and the result is spawned as a child
+// process.
+
+use std::path::Path;
+use std::process::Command;
+
+/// A tokenized `-x/--exec` command line with `{}`-style placeholders.
+#[derive(Debug, Clone)]
+pub struct CommandTemplate {
+    tokens: Vec<String>,
+}
+
+impl CommandTemplate {
+    pub fn new(tokens: Vec<String>) -> Option<CommandTemplate> {
+        if tokens.is_empty() {
+            None
+        } else {
+            Some(CommandTemplate { tokens })
+        }
+    }
+
+    /// Builds the `Command` for `path`, substituting:
+    /// `{}` -> full path, `{/}` -> file name, `{//}` -> parent dir,
+    /// `{.}` -> path without extension.
+    pub fn build(&self, path: &Path) -> Command {
+        let full = path.to_string_lossy().into_owned();
+        let file_name = path
+            .file_name()
+            .map(|f| f.to_string_lossy().into_owned())
+            .unwrap_or_else(|| full.clone());
+        let parent = path
+            .parent()
+            .filter(|p| !p.as_os_str().is_empty())
+            .map(|p| p.to_string_lossy().into_owned())
+            .unwrap_or_else(|| ".".to_string());
+        let no_ext = path.with_extension("").to_string_lossy().into_owned();
+
+        let substitute = |tok: &str| -> String {
+            tok.replace("{//}", &parent)
+                .replace("{/}", &file_name)
+                .replace("{.}", &no_ext)
+                .replace("{}", &full)
+        };
+
+        let mut cmd = Command::new(substitute(&self.tokens[0]));
+        for tok in &self.tokens[1..] {
+            cmd.arg(substitute(tok));
+        }
+        cmd
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn new_rejects_empty_tokens() {
+        assert!(CommandTemplate::new(Vec::new()).is_none());
+    }
+
+    #[test]
+    fn build_substitutes_placeholders() {
+        let template = CommandTemplate::new(vec![
+            "echo".to_string(),
+            "{}".to_string(),
+            "{/}".to_string(),
+            "{//}".to_string(),
+            "{.}".to_string(),
+        ])
+        .unwrap();
+        let cmd = template.build(Path::new("src/main.rs"));
+        let args: Vec<String> = cmd
+            .get_args()
+            .map(|a| a.to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(cmd.get_program().to_string_lossy(), "echo");
+        assert_eq!(args, vec!["src/main.rs", "main.rs", "src", "src/main"]);
+    }
+
+    #[test]
+    fn build_handles_path_with_no_parent() {
+        let template = CommandTemplate::new(vec!["cat".to_string(), "{//}".to_string()]).unwrap();
+        let cmd = template.build(Path::new("main.rs"));
+        let args: Vec<String> = cmd
+            .get_args()
+            .map(|a| a.to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(args, vec!["."]);
+    }
+}
@@ -0,0 +1,106 @@
+// File-name filtering for `--glob`/`--iglob`, translating shell globs into
+// anchored patterns for our own regex engine the same way fd/moros do:
+// escape existing `\`, turn `.` into `\.`, `*` into `.*`, `?` into `.`, then
+// wrap the whole thing in `^...$` so it only matches the full file name.
+
+use crate::matcher::Regex;
+
+#[derive(Debug)]
+struct CompiledGlob {
+    regex: Regex,
+    case_insensitive: bool,
+}
+
+/// A set of `--glob`/`--iglob` patterns. A file matches if any one of them
+/// matches its file name (not its full path).
+#[derive(Debug, Default)]
+pub struct GlobSet {
+    globs: Vec<CompiledGlob>,
+}
+
+impl GlobSet {
+    pub fn is_empty(&self) -> bool {
+        self.globs.is_empty()
+    }
+
+    pub fn add(&mut self, pattern: &str, case_insensitive: bool) {
+        let translated = translate(pattern);
+        self.globs.push(CompiledGlob {
+            regex: Regex::compile(&translated, case_insensitive),
+            case_insensitive,
+        });
+    }
+
+    pub fn matches(&self, file_name: &str) -> bool {
+        self.globs
+            .iter()
+            .any(|g| g.regex.find(file_name, g.case_insensitive).is_some())
+    }
+}
+
+fn translate(glob: &str) -> String {
+    let mut out = String::from("^");
+    for c in glob.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '.' => out.push_str("\\."),
+            '*' => out.push_str(".*"),
+            '?' => out.push('.'),
+            // Everything else our regex engine treats specially
+            // (`+ | ^ $ [ ]`) must be escaped too, or a glob like `a+b`
+            // picks up regex semantics it never asked for instead of
+            // matching those characters literally. `(` and `)` have no
+            // special meaning to the engine; escaping them is harmless.
+            '+' | '(' | ')' | '|' | '^' | '$' | '[' | ']' => {
+                out.push('\\');
+                out.push(c);
+            }
+            other => out.push(other),
+        }
+    }
+    out.push('$');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn star_and_question_wildcards_match() {
+        let mut set = GlobSet::default();
+        set.add("*.rs", false);
+        assert!(set.matches("main.rs"));
+        assert!(!set.matches("main.txt"));
+
+        let mut set = GlobSet::default();
+        set.add("log?.txt", false);
+        assert!(set.matches("log1.txt"));
+        assert!(!set.matches("log12.txt"));
+    }
+
+    #[test]
+    fn iglob_is_case_insensitive() {
+        let mut set = GlobSet::default();
+        set.add("*.RS", true);
+        assert!(set.matches("main.rs"));
+    }
+
+    #[test]
+    fn regex_metachars_in_glob_are_literal() {
+        let mut set = GlobSet::default();
+        set.add("a+b.txt", false);
+        assert!(set.matches("a+b.txt"));
+        assert!(!set.matches("aaab.txt"));
+
+        let mut set = GlobSet::default();
+        set.add("f(x).rs", false);
+        assert!(set.matches("f(x).rs"));
+    }
+
+    #[test]
+    fn default_glob_set_is_empty() {
+        let set = GlobSet::default();
+        assert!(set.is_empty());
+    }
+}
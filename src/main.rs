@@ -1,23 +1,92 @@
 // final
-use colored::Colorize;
+mod exec;
+mod glob;
+mod matcher;
+mod style;
+
+use exec::CommandTemplate;
+use glob::GlobSet;
+use matcher::Matcher;
 use std::env;
+use std::fmt::Write as _;
 use std::fs::File;
-use std::io::{self, BufRead, BufReader};
+use std::io::{BufRead, BufReader, IsTerminal};
 use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use style::MatchStyle;
 use walkdir::WalkDir;
 
+/// Process exit codes, following grep's documented exit-status contract
+/// (0 = at least one match, 1 = no matches) plus a distinct code for
+/// errors, the way fd's `ExitCode` does.
+#[derive(Debug, Clone, Copy)]
+enum ExitCode {
+    Success = 0,
+    NoMatch = 1,
+    Error = 2,
+}
+
+impl ExitCode {
+    fn as_i32(self) -> i32 {
+        self as i32
+    }
+}
+
+/// Tri-state color control, mirroring `grep --color=auto|always|never`.
+/// `Auto` colorizes only when stdout is a TTY, so piped output stays clean.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+enum ColorMode {
+    #[default]
+    Never,
+    Auto,
+    Always,
+}
+
+/// What happened while searching one file.
+#[derive(Debug, Default)]
+struct FileOutcome {
+    matched: bool,
+    error: bool,
+    // set when `cfg.exec` ran for this file and the child exited non-zero
+    exec_failed: bool,
+}
+
+/// Aggregate outcome across every searched file.
+#[derive(Debug, Default)]
+struct RunSummary {
+    any_match: bool,
+    had_error: bool,
+    exec_failed: bool,
+}
+
 #[derive(Debug, Default)]
 struct Config {
     // Position parameter
     pattern: String,
     paths: Vec<PathBuf>,
-    case_insensitive: bool, // -i
-    line_numbers: bool,     // -n
-    invert: bool,           // -v
-    recursive: bool,        // -r/-R
-    print_filenames: bool,  // -f
-    color: bool,            // -c
-    help: bool,             // -h/--help
+    case_insensitive: bool,        // -i
+    line_numbers: bool,            // -n
+    invert: bool,                  // -v
+    recursive: bool,               // -r/-R
+    print_filenames: bool,         // -f
+    color: bool,                   // resolved from color_mode before searching
+    color_mode: ColorMode,         // -c, --color
+    color_style: Option<String>,   // --color-style, falls back to $GREP_COLORS
+    regex: bool,                   // -e/--regex
+    globs: GlobSet,                // --glob/--iglob
+    exec: Option<CommandTemplate>, // -x/--exec
+    smart_case: bool,              // --smart-case
+    threads: usize,                // --threads, 0 = available_parallelism
+    help: bool,                    // -h/--help
+}
+
+/// Whether `pattern` contains any uppercase letter. Used by `--smart-case`
+/// to decide case sensitivity from the pattern itself.
+fn pattern_has_uppercase_char(pattern: &str) -> bool {
+    pattern.chars().any(|c| c.is_uppercase())
 }
 const HELP: &str = include_str!("../help.txt");
 
@@ -33,7 +102,10 @@ where
     let mut cfg = Config::default();
     let mut have_pattern = false;
 
-    for arg in iter.into_iter().skip(1).map(Into::into) {
+    let args: Vec<String> = iter.into_iter().skip(1).map(Into::into).collect();
+    let mut i = 0;
+    while i < args.len() {
+        let arg = &args[i];
         if arg == "-h" || arg == "--help" {
             cfg.help = true;
             return Some(cfg);
@@ -44,145 +116,438 @@ where
             "-v" => cfg.invert = true,
             "-r" | "-R" => cfg.recursive = true,
             "-f" => cfg.print_filenames = true,
-            "-c" => cfg.color = true,
+            "-c" => cfg.color_mode = ColorMode::Auto,
+            "--color" => {
+                // Only consume the next token as the color value if it
+                // actually is one; otherwise `--color` alone (like `-c`)
+                // means "auto" and the next token is left for `pattern`/
+                // `paths` to pick up.
+                cfg.color_mode = match args.get(i + 1).map(String::as_str) {
+                    Some("always") => {
+                        i += 1;
+                        ColorMode::Always
+                    }
+                    Some("never") => {
+                        i += 1;
+                        ColorMode::Never
+                    }
+                    Some("auto") => {
+                        i += 1;
+                        ColorMode::Auto
+                    }
+                    _ => ColorMode::Auto,
+                };
+            }
+            "--color-style" => {
+                i += 1;
+                cfg.color_style = args.get(i).cloned();
+            }
+            "-e" | "--regex" => cfg.regex = true,
+            "--smart-case" => cfg.smart_case = true,
+            "--glob" | "--iglob" => {
+                let case_insensitive = arg == "--iglob";
+                i += 1;
+                if let Some(pattern) = args.get(i) {
+                    cfg.globs.add(pattern, case_insensitive);
+                }
+            }
+            "--threads" => {
+                i += 1;
+                if let Some(n) = args.get(i).and_then(|s| s.parse().ok()) {
+                    cfg.threads = n;
+                }
+            }
+            "-x" | "--exec" => {
+                // everything after -x/--exec is the command template, not
+                // paths, so it must come last
+                cfg.exec = CommandTemplate::new(args[i + 1..].to_vec());
+                i = args.len();
+            }
             a if a.starts_with('-') => {}
             _ => {
                 if !have_pattern {
-                    cfg.pattern = arg;
+                    cfg.pattern = arg.clone();
                     have_pattern = true;
                 } else {
                     cfg.paths.push(PathBuf::from(arg));
                 }
             }
         }
+        i += 1;
     }
     Some(cfg)
 }
 
-fn run(cfg: Config) -> io::Result<()> {
+// a file passes the filter if there are no --glob/--iglob patterns, or the
+// file name (not the whole path) matches at least one of them
+fn passes_glob_filter(globs: &GlobSet, path: &Path) -> bool {
+    if globs.is_empty() {
+        return true;
+    }
+    match path.file_name() {
+        Some(name) => globs.matches(&name.to_string_lossy()),
+        None => false,
+    }
+}
+
+fn run(cfg: Config) -> RunSummary {
+    // resolve the effective case sensitivity from -i / --smart-case / the
+    // pattern itself: an explicit -i always wins, otherwise --smart-case
+    // makes an all-lowercase pattern case-insensitive
+    let mut cfg = cfg;
+    if cfg.smart_case && !cfg.case_insensitive {
+        cfg.case_insensitive = !pattern_has_uppercase_char(&cfg.pattern);
+    }
+
+    // resolve the color tri-state: Auto only colorizes when stdout is a
+    // TTY, so piping/redirecting output stays free of escape codes
+    cfg.color = match cfg.color_mode {
+        ColorMode::Always => true,
+        ColorMode::Never => false,
+        ColorMode::Auto => std::io::stdout().is_terminal(),
+    };
+    let style = cfg
+        .color_style
+        .clone()
+        .or_else(|| env::var("GREP_COLORS").ok())
+        .map(|spec| MatchStyle::parse(&spec))
+        .unwrap_or_default();
+
     //collect all files to be searched
     let mut files: Vec<PathBuf> = Vec::new();
 
     for p in &cfg.paths {
         if p.is_file() {
-            files.push(p.to_path_buf());
+            if passes_glob_filter(&cfg.globs, p) {
+                files.push(p.to_path_buf());
+            }
         } else if cfg.recursive {
             for entry in WalkDir::new(p).into_iter().filter_map(Result::ok) {
-                if entry.file_type().is_file() {
+                if entry.file_type().is_file() && passes_glob_filter(&cfg.globs, entry.path()) {
                     files.push(entry.path().to_path_buf());
                 }
             }
-        } else {
         }
     }
 
     files.sort_by(|a, b| a.to_string_lossy().cmp(&b.to_string_lossy()));
 
-    //search each file individually
-    for f in files {
-        search_one_file(&cfg, &f)?;
+    // compiled once per run, shared across every searched file
+    let cfg = Arc::new(cfg);
+    let matcher = Arc::new(Matcher::compile(
+        &cfg.pattern,
+        cfg.regex,
+        cfg.case_insensitive,
+    ));
+    let style = Arc::new(style);
+
+    let file_count = files.len();
+    let threads = if cfg.threads == 0 {
+        thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(1)
+    } else {
+        cfg.threads
     }
+    .clamp(1, file_count.max(1));
 
-    Ok(())
-}
+    // workers pull (index, path) off a bounded channel and search it
+    // independently; a coordinator buffers each file's output into a
+    // per-file String and prints the buffers back in the same sorted
+    // path order `files` is already in, keyed by index
+    let (work_tx, work_rx) = mpsc::sync_channel::<(usize, PathBuf)>(threads * 2);
+    let work_rx = Arc::new(Mutex::new(work_rx));
+    let (result_tx, result_rx) = mpsc::channel::<(usize, String, FileOutcome)>();
 
-fn search_one_file(cfg: &Config, path: &Path) -> io::Result<()> {
-    let file = File::open(path)?;
-    let reader = BufReader::new(file);
+    let workers: Vec<_> = (0..threads)
+        .map(|_| {
+            let work_rx = Arc::clone(&work_rx);
+            let result_tx = result_tx.clone();
+            let cfg = Arc::clone(&cfg);
+            let matcher = Arc::clone(&matcher);
+            let style = Arc::clone(&style);
+            thread::spawn(move || loop {
+                let next = work_rx.lock().unwrap().recv();
+                match next {
+                    Ok((idx, path)) => {
+                        let (buf, outcome) = search_one_file(&cfg, &matcher, &style, &path);
+                        if result_tx.send((idx, buf, outcome)).is_err() {
+                            break;
+                        }
+                    }
+                    Err(_) => break,
+                }
+            })
+        })
+        .collect();
+    drop(result_tx);
 
-    // -i
-    let pat_lc = if cfg.case_insensitive {
-        Some(cfg.pattern.to_lowercase())
-    } else {
-        None
+    for (idx, path) in files.into_iter().enumerate() {
+        if work_tx.send((idx, path)).is_err() {
+            break;
+        }
+    }
+    drop(work_tx);
+
+    let mut buffers: Vec<Option<(String, FileOutcome)>> = (0..file_count).map(|_| None).collect();
+    for (idx, buf, outcome) in result_rx {
+        buffers[idx] = Some((buf, outcome));
+    }
+    // A panicking worker drops its in-flight file's result, leaving that
+    // slot `None`; without noticing the panic here, that file's output
+    // (and any match it found) silently disappears and the run can report
+    // success when it actually lost data. Surface it as a hard error.
+    let mut worker_panicked = false;
+    for worker in workers {
+        if worker.join().is_err() {
+            worker_panicked = true;
+        }
+    }
+
+    let mut summary = RunSummary::default();
+    for entry in buffers.into_iter().flatten() {
+        let (buf, outcome) = entry;
+        print!("{buf}");
+        summary.any_match |= outcome.matched;
+        summary.had_error |= outcome.error;
+        summary.exec_failed |= outcome.exec_failed;
+    }
+    summary.had_error |= worker_panicked;
+
+    summary
+}
+
+// searches one file, returning its buffered output (so the coordinator can
+// print files back in sorted order regardless of which worker finished
+// first) alongside what happened while searching it
+fn search_one_file(
+    cfg: &Config,
+    matcher: &Matcher,
+    style: &MatchStyle,
+    path: &Path,
+) -> (String, FileOutcome) {
+    let mut buf = String::new();
+    let file = match File::open(path) {
+        Ok(f) => f,
+        Err(e) => {
+            eprintln!("grep: {}: {}", path.display(), e);
+            return (
+                buf,
+                FileOutcome {
+                    error: true,
+                    ..FileOutcome::default()
+                },
+            );
+        }
     };
+    let reader = BufReader::new(file);
 
     let path_str = path.to_string_lossy().replace('\\', "/");
+    let mut outcome = FileOutcome::default();
 
     for (idx, line_res) in reader.lines().enumerate() {
-        let line = line_res?;
+        let line = match line_res {
+            Ok(l) => l,
+            Err(e) => {
+                eprintln!("grep: {}: {}", path.display(), e);
+                outcome.error = true;
+                break;
+            }
+        };
 
         // determine whether it matches
-        let is_match = if let Some(ref pat) = pat_lc {
-            line.to_lowercase().contains(pat)
-        } else {
-            line.contains(&cfg.pattern)
-        };
+        let is_match = matcher.is_match(&line, cfg.case_insensitive);
 
         // -v：
         let should_print = if cfg.invert { !is_match } else { is_match };
         if !should_print {
             continue;
         }
+        outcome.matched = true;
+
+        if cfg.exec.is_some() {
+            // -x/--exec suppresses normal line output
+            continue;
+        }
 
         let display_line = if cfg.color && !cfg.invert && is_match {
-            highlight_line(&line, &cfg.pattern, cfg.case_insensitive)
+            highlight_line(&line, matcher, cfg.case_insensitive, style)
         } else {
             line.clone()
         };
 
         if cfg.print_filenames && cfg.line_numbers {
-            println!("{}: {}: {}", path_str, idx + 1, display_line);
+            let _ = writeln!(buf, "{}: {}: {}", path_str, idx + 1, display_line);
         } else if cfg.print_filenames {
-            println!("{}: {}", path_str, display_line);
+            let _ = writeln!(buf, "{}: {}", path_str, display_line);
         } else if cfg.line_numbers {
-            println!("{}: {}", idx + 1, display_line);
+            let _ = writeln!(buf, "{}: {}", idx + 1, display_line);
         } else {
-            println!("{}", display_line);
+            let _ = writeln!(buf, "{}", display_line);
         }
     }
 
-    Ok(())
-}
-
-// highlight non-overlapping matches in red
-fn highlight_line(line: &str, pattern: &str, case_insensitive: bool) -> String {
-    if pattern.is_empty() {
-        return line.to_string();
-    }
-
-    if !case_insensitive {
-        let mut out = String::new();
-        let mut i = 0;
-        while let Some(pos) = line[i..].find(pattern) {
-            let start = i + pos;
-            let end = start + pattern.len();
-            out.push_str(&line[i..start]);
-            out.push_str(&line[start..end].red().to_string());
-            i = end;
+    if let Some(template) = &cfg.exec {
+        if outcome.matched {
+            // Capture rather than inherit the child's stdout: workers run
+            // in parallel (see chunk0-6), and inheriting stdout would let
+            // concurrently exec'd children interleave their output directly,
+            // breaking the sorted-path-order guarantee the buffered `buf`
+            // print path upholds for everything else.
+            match template.build(path).output() {
+                Ok(output) => {
+                    buf.push_str(&String::from_utf8_lossy(&output.stdout));
+                    if !output.stderr.is_empty() {
+                        eprint!("{}", String::from_utf8_lossy(&output.stderr));
+                    }
+                    outcome.exec_failed = !output.status.success();
+                }
+                Err(e) => {
+                    eprintln!("grep: {}: failed to exec command: {}", path.display(), e);
+                    outcome.error = true;
+                }
+            }
         }
-        out.push_str(&line[i..]);
-        return out;
     }
 
-    let ll = line.to_lowercase();
-    let pp = pattern.to_lowercase();
+    (buf, outcome)
+}
+
+// highlight non-overlapping matches using the resolved match style
+fn highlight_line(
+    line: &str,
+    matcher: &Matcher,
+    case_insensitive: bool,
+    style: &MatchStyle,
+) -> String {
     let mut out = String::new();
     let mut i = 0;
-    while let Some(pos) = ll[i..].find(&pp) {
-        let start = i + pos;
-        let end = start + pp.len();
-        out.push_str(&line[i..start]);
-        out.push_str(&line[start..end].red().to_string());
-        i = end;
+    while i <= line.len() {
+        match matcher.find(&line[i..], case_insensitive) {
+            // Defend against a buggy matcher returning offsets that don't
+            // land on char boundaries (or overrun the slice) rather than
+            // panicking on an untrusted index: treat it as no match.
+            Some((start, end)) if !is_char_boundary_offset(line, i, start, end) => break,
+            Some((start, end)) if start != end => {
+                out.push_str(&line[i..i + start]);
+                out.push_str(&style.apply(&line[i + start..i + end]));
+                i += end;
+            }
+            // zero-length match (e.g. `a*` with no `a`s): step one char so we
+            // don't loop forever re-matching the same empty span
+            Some((start, _)) => {
+                out.push_str(&line[i..i + start]);
+                match line[i + start..].chars().next() {
+                    Some(ch) => {
+                        out.push_str(&line[i + start..i + start + ch.len_utf8()]);
+                        i += start + ch.len_utf8();
+                    }
+                    None => {
+                        i += start;
+                        break;
+                    }
+                }
+            }
+            None => break,
+        }
     }
     out.push_str(&line[i..]);
     out
 }
 
+/// Whether `line[i + start..i + end]` is in bounds and falls on char
+/// boundaries, i.e. safe to slice without panicking.
+fn is_char_boundary_offset(line: &str, i: usize, start: usize, end: usize) -> bool {
+    match (i.checked_add(start), i.checked_add(end)) {
+        (Some(a), Some(b)) => {
+            b <= line.len() && line.is_char_boundary(a) && line.is_char_boundary(b)
+        }
+        _ => false,
+    }
+}
+
+/// Resolves the run's aggregate outcome into the documented exit-status
+/// contract: errors outrank a clean no-match, which outranks success.
+fn resolve_exit_code(summary: &RunSummary, has_exec: bool) -> ExitCode {
+    if summary.had_error || (has_exec && summary.exec_failed) {
+        ExitCode::Error
+    } else if !summary.any_match {
+        ExitCode::NoMatch
+    } else {
+        ExitCode::Success
+    }
+}
+
 fn main() {
-    let cfg = parse_args(env::args()).expect("failed to parse args");
+    let cfg = match parse_args(env::args()) {
+        Some(cfg) => cfg,
+        None => {
+            eprintln!("grep: failed to parse arguments");
+            process::exit(ExitCode::Error.as_i32());
+        }
+    };
 
     if cfg.help {
         print_help();
-        return;
+        process::exit(ExitCode::Success.as_i32());
     }
     if cfg.pattern.is_empty() || cfg.paths.is_empty() {
         print_help();
-        return;
+        process::exit(ExitCode::Error.as_i32());
+    }
+
+    let has_exec = cfg.exec.is_some();
+    let summary = run(cfg);
+
+    process::exit(resolve_exit_code(&summary, has_exec).as_i32());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exit_code_success_when_matched_with_no_errors() {
+        let summary = RunSummary {
+            any_match: true,
+            had_error: false,
+            exec_failed: false,
+        };
+        assert_eq!(resolve_exit_code(&summary, false) as i32, ExitCode::Success as i32);
+    }
+
+    #[test]
+    fn exit_code_no_match_when_clean_but_no_hits() {
+        let summary = RunSummary::default();
+        assert_eq!(resolve_exit_code(&summary, false) as i32, ExitCode::NoMatch as i32);
+    }
+
+    #[test]
+    fn exit_code_error_outranks_a_match() {
+        let summary = RunSummary {
+            any_match: true,
+            had_error: true,
+            exec_failed: false,
+        };
+        assert_eq!(resolve_exit_code(&summary, false) as i32, ExitCode::Error as i32);
+    }
+
+    #[test]
+    fn exit_code_error_when_exec_failed_and_exec_was_requested() {
+        let summary = RunSummary {
+            any_match: true,
+            had_error: false,
+            exec_failed: true,
+        };
+        assert_eq!(resolve_exit_code(&summary, true) as i32, ExitCode::Error as i32);
+        // exec_failed is irrelevant when -x/--exec wasn't even used
+        assert_eq!(resolve_exit_code(&summary, false) as i32, ExitCode::Success as i32);
     }
 
-    if let Err(e) = run(cfg) {
-        panic!("error: {e}");
+    #[test]
+    fn smart_case_triggers_only_on_an_uppercase_pattern() {
+        assert!(!pattern_has_uppercase_char("hello"));
+        assert!(pattern_has_uppercase_char("Hello"));
+        assert!(!pattern_has_uppercase_char("h3ll0_w0rld"));
     }
 }
@@ -0,0 +1,491 @@
+// A small, self-contained regex engine used by `-e/--regex`.
+//
+// Patterns are parsed into an AST and compiled into a Thompson-style NFA
+// program (Char/Any/Class/Split/Jump/Match instructions). Matching runs a
+// Pike VM: a set of active thread program-counters is advanced one input
+// char at a time, so there is no backtracking and no risk of exponential
+// blowup on pathological patterns.
+
+/// Folds `c` to lowercase for case-insensitive comparison, collapsing any
+/// multi-char expansion (e.g. `İ` -> `i̇`) down to its first char. `Regex` and
+/// `Matcher::Literal` both compare original-string chars through this rather
+/// than lowercasing whole strings, which keeps byte offsets valid for the
+/// string callers actually slice.
+fn fold_char(c: char) -> char {
+    c.to_lowercase().next().unwrap_or(c)
+}
+
+/// One instruction in the compiled NFA program.
+#[derive(Debug, Clone)]
+enum Inst {
+    Char(char),
+    Any,
+    /// Character class ranges plus whether the class is negated (`[^...]`).
+    Class(Vec<(char, char)>, bool),
+    Split(usize, usize),
+    Jump(usize),
+    StartAnchor,
+    EndAnchor,
+    Match,
+}
+
+#[derive(Debug)]
+enum Ast {
+    Char(char),
+    Any,
+    Class(Vec<(char, char)>, bool),
+    Concat(Vec<Ast>),
+    Alt(Vec<Ast>),
+    Star(Box<Ast>),
+    Plus(Box<Ast>),
+    Question(Box<Ast>),
+    StartAnchor,
+    EndAnchor,
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek();
+        if c.is_some() {
+            self.pos += 1;
+        }
+        c
+    }
+
+    fn parse_alt(&mut self) -> Ast {
+        let mut branches = vec![self.parse_concat()];
+        while self.peek() == Some('|') {
+            self.bump();
+            branches.push(self.parse_concat());
+        }
+        if branches.len() == 1 {
+            branches.pop().unwrap()
+        } else {
+            Ast::Alt(branches)
+        }
+    }
+
+    fn parse_concat(&mut self) -> Ast {
+        let mut parts = Vec::new();
+        while let Some(c) = self.peek() {
+            if c == '|' {
+                break;
+            }
+            parts.push(self.parse_repeat());
+        }
+        Ast::Concat(parts)
+    }
+
+    fn parse_repeat(&mut self) -> Ast {
+        let atom = self.parse_atom();
+        match self.peek() {
+            Some('*') => {
+                self.bump();
+                Ast::Star(Box::new(atom))
+            }
+            Some('+') => {
+                self.bump();
+                Ast::Plus(Box::new(atom))
+            }
+            Some('?') => {
+                self.bump();
+                Ast::Question(Box::new(atom))
+            }
+            _ => atom,
+        }
+    }
+
+    fn parse_atom(&mut self) -> Ast {
+        match self.bump() {
+            Some('.') => Ast::Any,
+            Some('^') => Ast::StartAnchor,
+            Some('$') => Ast::EndAnchor,
+            Some('\\') => Ast::Char(self.bump().unwrap_or('\\')),
+            Some('[') => self.parse_class(),
+            Some(c) => Ast::Char(c),
+            None => Ast::Concat(Vec::new()),
+        }
+    }
+
+    fn parse_class(&mut self) -> Ast {
+        let negated = if self.peek() == Some('^') {
+            self.bump();
+            true
+        } else {
+            false
+        };
+        let mut ranges = Vec::new();
+        // A `]` as the very first character of the class is a literal.
+        let mut first = true;
+        loop {
+            match self.peek() {
+                None => break,
+                Some(']') if !first => {
+                    self.bump();
+                    break;
+                }
+                Some(c) => {
+                    self.bump();
+                    let lo = if c == '\\' {
+                        self.bump().unwrap_or('\\')
+                    } else {
+                        c
+                    };
+                    if self.peek() == Some('-') && self.chars.get(self.pos + 1) != Some(&']') {
+                        self.bump();
+                        let hi = match self.bump() {
+                            Some('\\') => self.bump().unwrap_or('\\'),
+                            Some(c) => c,
+                            None => lo,
+                        };
+                        ranges.push((lo, hi));
+                    } else {
+                        ranges.push((lo, lo));
+                    }
+                }
+            }
+            first = false;
+        }
+        Ast::Class(ranges, negated)
+    }
+}
+
+struct Compiler {
+    prog: Vec<Inst>,
+}
+
+impl Compiler {
+    fn compile(&mut self, ast: &Ast) {
+        match ast {
+            Ast::Char(c) => self.prog.push(Inst::Char(*c)),
+            Ast::Any => self.prog.push(Inst::Any),
+            Ast::Class(ranges, neg) => self.prog.push(Inst::Class(ranges.clone(), *neg)),
+            Ast::StartAnchor => self.prog.push(Inst::StartAnchor),
+            Ast::EndAnchor => self.prog.push(Inst::EndAnchor),
+            Ast::Concat(parts) => {
+                for p in parts {
+                    self.compile(p);
+                }
+            }
+            Ast::Alt(branches) => {
+                let mut jumps = Vec::new();
+                for (i, branch) in branches.iter().enumerate() {
+                    if i + 1 < branches.len() {
+                        let split_at = self.prog.len();
+                        self.prog.push(Inst::Split(0, 0));
+                        let l1 = self.prog.len();
+                        self.compile(branch);
+                        let jump_at = self.prog.len();
+                        self.prog.push(Inst::Jump(0));
+                        jumps.push(jump_at);
+                        let l2 = self.prog.len();
+                        self.prog[split_at] = Inst::Split(l1, l2);
+                    } else {
+                        self.compile(branch);
+                    }
+                }
+                let end = self.prog.len();
+                for idx in jumps {
+                    self.prog[idx] = Inst::Jump(end);
+                }
+            }
+            Ast::Star(inner) => {
+                let split_at = self.prog.len();
+                self.prog.push(Inst::Split(0, 0));
+                let l1 = self.prog.len();
+                self.compile(inner);
+                self.prog.push(Inst::Jump(split_at));
+                let l2 = self.prog.len();
+                self.prog[split_at] = Inst::Split(l1, l2);
+            }
+            Ast::Plus(inner) => {
+                let l1 = self.prog.len();
+                self.compile(inner);
+                let split_at = self.prog.len();
+                self.prog.push(Inst::Split(0, 0));
+                let l2 = self.prog.len();
+                self.prog[split_at] = Inst::Split(l1, l2);
+            }
+            Ast::Question(inner) => {
+                let split_at = self.prog.len();
+                self.prog.push(Inst::Split(0, 0));
+                let l1 = self.prog.len();
+                self.compile(inner);
+                let l2 = self.prog.len();
+                self.prog[split_at] = Inst::Split(l1, l2);
+            }
+        }
+    }
+}
+
+/// A compiled regex program.
+#[derive(Debug)]
+pub struct Regex {
+    prog: Vec<Inst>,
+}
+
+impl Regex {
+    /// Parse and compile `pattern`. `case_insensitive` lowercases the whole
+    /// pattern up front, since none of the supported metacharacters are
+    /// uppercase letters, this only affects literal chars and class ranges.
+    pub fn compile(pattern: &str, case_insensitive: bool) -> Regex {
+        let folded;
+        let pattern = if case_insensitive {
+            folded = pattern.to_lowercase();
+            folded.as_str()
+        } else {
+            pattern
+        };
+        let mut parser = Parser {
+            chars: pattern.chars().collect(),
+            pos: 0,
+        };
+        let ast = parser.parse_alt();
+        let mut compiler = Compiler { prog: Vec::new() };
+        compiler.compile(&ast);
+        compiler.prog.push(Inst::Match);
+        Regex {
+            prog: compiler.prog,
+        }
+    }
+
+    /// Search `text` for the earliest, longest match. Returns byte offsets
+    /// into `text` so callers can slice it directly for highlighting.
+    ///
+    /// Matching always walks `text`'s own chars (never a lowercased copy):
+    /// `to_lowercase()` can change a string's byte *and* char length (e.g.
+    /// `İ` -> `i̇`), which would desync any offsets computed against a folded
+    /// copy from the original string callers slice. Case-insensitivity is
+    /// instead handled per-char via `fold_char` while walking the NFA.
+    pub fn find(&self, text: &str, case_insensitive: bool) -> Option<(usize, usize)> {
+        let char_offsets: Vec<usize> = text
+            .char_indices()
+            .map(|(i, _)| i)
+            .chain(std::iter::once(text.len()))
+            .collect();
+        let chars: Vec<char> = text.chars().collect();
+
+        for start in 0..=chars.len() {
+            if let Some(end) = self.run_from(&chars, start, case_insensitive) {
+                return Some((char_offsets[start], char_offsets[end]));
+            }
+        }
+        None
+    }
+
+    /// Runs the NFA from `start`, returning the furthest position (in char
+    /// indices) reached by any thread that lands on `Match`.
+    fn run_from(&self, chars: &[char], start: usize, case_insensitive: bool) -> Option<usize> {
+        let len = chars.len();
+        let mut gen = 0u32;
+        let mut marks = vec![0u32; self.prog.len()];
+        let mut clist = Vec::new();
+        gen += 1;
+        self.add_thread(&mut clist, &mut marks, gen, 0, start, len);
+
+        let mut matched_end = None;
+        let mut pos = start;
+        loop {
+            if clist.iter().any(|&pc| matches!(self.prog[pc], Inst::Match)) {
+                matched_end = Some(pos);
+            }
+            if pos >= len || clist.is_empty() {
+                break;
+            }
+            let c = if case_insensitive {
+                fold_char(chars[pos])
+            } else {
+                chars[pos]
+            };
+            let mut nlist = Vec::new();
+            gen += 1;
+            for &pc in &clist {
+                match &self.prog[pc] {
+                    Inst::Char(ch) if *ch == c => {
+                        self.add_thread(&mut nlist, &mut marks, gen, pc + 1, pos + 1, len)
+                    }
+                    Inst::Any => self.add_thread(&mut nlist, &mut marks, gen, pc + 1, pos + 1, len),
+                    Inst::Class(ranges, neg) => {
+                        let inside = ranges.iter().any(|&(lo, hi)| c >= lo && c <= hi);
+                        if inside != *neg {
+                            self.add_thread(&mut nlist, &mut marks, gen, pc + 1, pos + 1, len);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            clist = nlist;
+            pos += 1;
+        }
+        matched_end
+    }
+
+    /// Follows Split/Jump/anchors (which consume no input) and pushes the
+    /// resulting Char/Any/Class/Match program counters onto `list`, each at
+    /// most once per step (tracked via `marks`/`gen`).
+    fn add_thread(
+        &self,
+        list: &mut Vec<usize>,
+        marks: &mut [u32],
+        gen: u32,
+        pc: usize,
+        pos: usize,
+        len: usize,
+    ) {
+        if marks[pc] == gen {
+            return;
+        }
+        marks[pc] = gen;
+        match &self.prog[pc] {
+            Inst::Jump(target) => self.add_thread(list, marks, gen, *target, pos, len),
+            Inst::Split(a, b) => {
+                self.add_thread(list, marks, gen, *a, pos, len);
+                self.add_thread(list, marks, gen, *b, pos, len);
+            }
+            Inst::StartAnchor => {
+                if pos == 0 {
+                    self.add_thread(list, marks, gen, pc + 1, pos, len);
+                }
+            }
+            Inst::EndAnchor => {
+                if pos == len {
+                    self.add_thread(list, marks, gen, pc + 1, pos, len);
+                }
+            }
+            _ => list.push(pc),
+        }
+    }
+}
+
+/// The pattern matching strategy selected by `Config.regex`: either the
+/// historical plain substring search, or the NFA-backed regex engine.
+#[derive(Debug)]
+pub enum Matcher {
+    Literal(String),
+    Regex(Regex),
+}
+
+impl Matcher {
+    /// Compile `pattern` once per run into the matcher the rest of the
+    /// search should use.
+    pub fn compile(pattern: &str, regex: bool, case_insensitive: bool) -> Matcher {
+        if regex {
+            Matcher::Regex(Regex::compile(pattern, case_insensitive))
+        } else {
+            let pattern = if case_insensitive {
+                pattern.to_lowercase()
+            } else {
+                pattern.to_string()
+            };
+            Matcher::Literal(pattern)
+        }
+    }
+
+    pub fn is_match(&self, line: &str, case_insensitive: bool) -> bool {
+        self.find(line, case_insensitive).is_some()
+    }
+
+    /// Finds the first match in `line`, returning byte offsets.
+    pub fn find(&self, line: &str, case_insensitive: bool) -> Option<(usize, usize)> {
+        match self {
+            Matcher::Literal(pattern) => {
+                if pattern.is_empty() {
+                    return None;
+                }
+                if case_insensitive {
+                    // `pattern` was lowercased once at compile time (see
+                    // `Matcher::compile`); fold `line`'s chars one at a time
+                    // instead of lowercasing it wholesale so byte offsets
+                    // stay valid for the original `line`.
+                    let pat_chars: Vec<char> = pattern.chars().collect();
+                    let offsets: Vec<usize> = line
+                        .char_indices()
+                        .map(|(i, _)| i)
+                        .chain(std::iter::once(line.len()))
+                        .collect();
+                    let chars: Vec<char> = line.chars().collect();
+                    for start in 0..=chars.len().saturating_sub(pat_chars.len()) {
+                        if pat_chars.is_empty() {
+                            break;
+                        }
+                        let end = start + pat_chars.len();
+                        if chars[start..end]
+                            .iter()
+                            .zip(&pat_chars)
+                            .all(|(&c, &p)| fold_char(c) == p)
+                        {
+                            return Some((offsets[start], offsets[end]));
+                        }
+                    }
+                    None
+                } else {
+                    line.find(pattern.as_str())
+                        .map(|start| (start, start + pattern.len()))
+                }
+            }
+            Matcher::Regex(re) => re.find(line, case_insensitive),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn literal_matcher_finds_substring() {
+        let m = Matcher::compile("world", false, false);
+        assert_eq!(m.find("hello world", false), Some((6, 11)));
+        assert!(!m.is_match("hello there", false));
+    }
+
+    #[test]
+    fn literal_matcher_case_insensitive_keeps_original_offsets() {
+        let m = Matcher::compile("WORLD", false, true);
+        assert_eq!(m.find("hello World", true), Some((6, 11)));
+    }
+
+    #[test]
+    fn case_insensitive_match_survives_case_folding_length_change() {
+        // U+0130 (İ) lowercases to a 2-char, 3-byte sequence ("i̇"), one byte
+        // longer than the 2-byte original. A matcher that lowercases the
+        // whole haystack and reuses the resulting offsets against the
+        // original string would panic or misalign here.
+        let m = Matcher::compile("x", false, true);
+        let text = "\u{0130}x";
+        let (start, end) = m.find(text, true).expect("should match the trailing x");
+        assert_eq!(&text[start..end], "x");
+    }
+
+    #[test]
+    fn regex_supports_alternation_and_repetition() {
+        let re = Regex::compile("ab+|c", false);
+        assert_eq!(re.find("zzabbbz", false), Some((2, 6)));
+        assert_eq!(re.find("zzcz", false), Some((2, 3)));
+        assert_eq!(re.find("zzz", false), None);
+    }
+
+    #[test]
+    fn regex_supports_char_classes_and_anchors() {
+        let re = Regex::compile("^[0-9]+$", false);
+        assert!(re.find("12345", false).is_some());
+        assert!(re.find("12a45", false).is_none());
+    }
+
+    #[test]
+    fn regex_is_case_insensitive_without_lowercasing_haystack() {
+        let re = Regex::compile("ABC", true);
+        let text = "\u{0130}abc";
+        let (start, end) = re.find(text, true).expect("should match");
+        assert_eq!(&text[start..end], "abc");
+    }
+}
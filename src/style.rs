@@ -0,0 +1,42 @@
+// Match highlighting style, configurable via `GREP_COLORS` or
+// `--color-style` instead of a hardcoded color, the way LS_COLORS lets fd
+// and exa customize their output.
+//
+// Only the `ms=` (match style) field is supported, since that's the only
+// thing `highlight_line` draws: a semicolon-separated list of SGR codes,
+// e.g. `ms=1;31` for bold red.
+
+#[derive(Debug, Clone)]
+pub struct MatchStyle {
+    codes: String,
+}
+
+impl MatchStyle {
+    /// Parses a `GREP_COLORS`-style spec (colon-separated `key=value`
+    /// fields) and pulls out `ms=`. Unknown fields are ignored.
+    pub fn parse(spec: &str) -> MatchStyle {
+        for field in spec.split(':') {
+            if let Some(codes) = field.strip_prefix("ms=") {
+                if !codes.is_empty() {
+                    return MatchStyle {
+                        codes: codes.to_string(),
+                    };
+                }
+            }
+        }
+        MatchStyle::default()
+    }
+
+    pub fn apply(&self, text: &str) -> String {
+        format!("\x1b[{}m{}\x1b[0m", self.codes, text)
+    }
+}
+
+impl Default for MatchStyle {
+    // bold red (`1;31`); the old hardcoded `.red()` was plain red (`31`)
+    fn default() -> MatchStyle {
+        MatchStyle {
+            codes: "1;31".to_string(),
+        }
+    }
+}